@@ -0,0 +1,239 @@
+//! Property-based fuzzing over `Evm::call_raw`, driven by a function's ABI input types.
+
+use crate::Evm;
+
+use ethers::{
+    abi::{self, Function, ParamType, Token},
+    types::{Address, Bytes, U256},
+};
+use proptest::{
+    prelude::*,
+    test_runner::{Config as ProptestConfig, RngAlgorithm, TestCaseError, TestRng, TestRunner},
+};
+
+use eyre::Result;
+
+use std::cell::RefCell;
+
+/// The outcome of a single fuzz run: a calldata tuple that made the target function revert.
+#[derive(Debug, Clone)]
+pub struct CounterExample {
+    pub calldata: Bytes,
+    pub args: Vec<Token>,
+    pub reason: String,
+    pub gas_used: u64,
+    /// The seed of the proptest RNG that generated this run's inputs: a `TestRunner` built
+    /// from `TestRng::from_seed(RngAlgorithm::ChaCha, &seed)` with the same number of cases
+    /// generates the same inputs in the same order. `fuzz` itself takes no seed and draws a
+    /// new one on every call.
+    pub seed: [u8; 32],
+}
+
+/// Decodes a revert's return data into a human-readable reason, falling back to the raw
+/// `ExitReason` debug repr for bytecode that doesn't revert with a standard `Error(string)`
+/// payload (e.g. a panic selector, a custom error, or an empty revert).
+fn decode_revert_reason<R: std::fmt::Debug>(retdata: &Bytes, status: &R) -> String {
+    // Standard Solidity `require`/`revert("...")` reverts are ABI-encoded as
+    // `Error(string)`, i.e. selector `0x08c379a0` followed by the encoded string.
+    if retdata.len() >= 4 && retdata[..4] == [0x08, 0xc3, 0x79, 0xa0] {
+        if let Ok(tokens) = abi::decode(&[ParamType::String], &retdata[4..]) {
+            if let Some(Token::String(reason)) = tokens.into_iter().next() {
+                return reason
+            }
+        }
+    }
+    format!("{:?}", status)
+}
+
+/// A seed for a fuzz run's RNG, derived from the current time, so every [`CounterExample`]
+/// carries the seed its inputs were generated from (as opposed to proptest's default of
+/// seeding from OS entropy, which it never hands back to the caller).
+fn fresh_seed() -> [u8; 32] {
+    use std::time::{SystemTime, UNIX_EPOCH};
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let mut seed = [0u8; 32];
+    seed[..16].copy_from_slice(&nanos.to_le_bytes());
+    seed
+}
+
+/// Fuzzes `func` on the contract deployed at `to`, generating `runs` randomized calldata
+/// tuples from its ABI input types and treating any revert as a failing case. Returns the
+/// shrunk counterexample (and the seed that reproduces it) on the first failure, or `None` if
+/// every run succeeded.
+pub fn fuzz<S, E: Evm<S>>(
+    evm: &mut E,
+    func: &Function,
+    from: Address,
+    to: Address,
+    runs: u32,
+) -> Result<Option<CounterExample>> {
+    let strategy = params_strategy(&func.inputs.iter().map(|p| p.kind.clone()).collect::<Vec<_>>());
+
+    let seed = fresh_seed();
+    let rng = TestRng::from_seed(RngAlgorithm::ChaCha, &seed);
+    let mut runner = TestRunner::new_with_rng(ProptestConfig { cases: runs, ..Default::default() }, rng);
+
+    // `evm` is one stateful instance shared across every trial (each call runs with
+    // `is_static: false`, so state-modifying effects persist run-to-run and there's no
+    // snapshot/revert between trials). That means a failing call's `retdata`/`gas_used` can
+    // only be trusted if captured *at the moment it happens* -- replaying the shrunk calldata
+    // against `evm` afterwards would run it against state that has moved on since, and for a
+    // non-idempotent target can report a different reason, a different gas cost, or even a
+    // different pass/fail outcome than what proptest actually found.
+    let failure: RefCell<Option<(String, u64)>> = RefCell::new(None);
+    let result = runner.run(&strategy, |tokens| {
+        let calldata = func.encode_input(&tokens).map_err(|e| TestCaseError::fail(e.to_string()))?;
+        let mut calldata_with_selector = func.short_signature().to_vec();
+        calldata_with_selector.extend(calldata);
+
+        let (retdata, status, gas_used, _) = evm
+            .call_raw(from, to, calldata_with_selector.into(), U256::zero(), false)
+            .map_err(|e| TestCaseError::fail(e.to_string()))?;
+
+        if E::is_fail(&status) {
+            *failure.borrow_mut() = Some((decode_revert_reason(&retdata, &status), gas_used));
+            return Err(TestCaseError::fail(format!("{:?}", status)))
+        }
+        Ok(())
+    });
+
+    match result {
+        Ok(()) => Ok(None),
+        Err(err) => {
+            let tokens = err.case().clone();
+            let calldata = func.encode_input(&tokens)?;
+            let mut calldata_with_selector = func.short_signature().to_vec();
+            calldata_with_selector.extend(calldata);
+
+            let (reason, gas_used) = failure
+                .into_inner()
+                .expect("a failing run always records its outcome before returning Err");
+
+            Ok(Some(CounterExample {
+                calldata: calldata_with_selector.into(),
+                args: tokens,
+                reason,
+                gas_used,
+                seed,
+            }))
+        }
+    }
+}
+
+/// Builds a `proptest` strategy producing a `Vec<Token>` matching `params`, one strategy per
+/// Solidity parameter type, recursing into array/tuple element types.
+fn params_strategy(params: &[ParamType]) -> impl Strategy<Value = Vec<Token>> {
+    params.iter().map(param_strategy).collect::<Vec<_>>()
+}
+
+fn param_strategy(param: &ParamType) -> BoxedStrategy<Token> {
+    match param {
+        ParamType::Bool => any::<bool>().prop_map(Token::Bool).boxed(),
+        ParamType::Address => any::<[u8; 20]>().prop_map(|b| Token::Address(b.into())).boxed(),
+        ParamType::Uint(size) => uint_strategy(*size).prop_map(Token::Uint).boxed(),
+        ParamType::Int(size) => int_strategy(*size).prop_map(Token::Int).boxed(),
+        ParamType::Bytes => any::<Vec<u8>>().prop_map(Token::Bytes).boxed(),
+        ParamType::String => ".*".prop_map(Token::String).boxed(),
+        ParamType::FixedBytes(size) => {
+            let size = *size;
+            proptest::collection::vec(any::<u8>(), size).prop_map(Token::FixedBytes).boxed()
+        }
+        ParamType::Array(inner) => {
+            proptest::collection::vec(param_strategy(inner), 0..16).prop_map(Token::Array).boxed()
+        }
+        ParamType::FixedArray(inner, size) => {
+            proptest::collection::vec(param_strategy(inner), *size).prop_map(Token::FixedArray).boxed()
+        }
+        ParamType::Tuple(inner) => {
+            params_strategy(inner).prop_map(Token::Tuple).boxed()
+        }
+    }
+}
+
+/// A `U256` bounded to the range representable by a Solidity integer of `bits` width.
+fn uint_strategy(bits: usize) -> impl Strategy<Value = U256> {
+    any::<[u8; 32]>().prop_map(move |bytes| {
+        let max_shift = 256 - bits;
+        (U256::from_big_endian(&bytes) << max_shift) >> max_shift
+    })
+}
+
+/// A `U256` holding the two's-complement ABI encoding of a signed Solidity integer of `bits`
+/// width. Unlike `uint_strategy`, negative values are sign-extended up to the full 256 bits
+/// (every bit above `bits` set to 1) rather than zero-extended, matching how Solidity actually
+/// ABI-encodes a negative `intN` — without this, generated negative values fail ABI decoding on
+/// the other end and every negative-guarded branch goes untested.
+fn int_strategy(bits: usize) -> impl Strategy<Value = U256> {
+    uint_strategy(bits).prop_map(move |masked| {
+        let sign_bit = U256::one() << (bits - 1);
+        if masked & sign_bit == U256::zero() || bits == 256 {
+            masked
+        } else {
+            masked | (U256::MAX << bits)
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sputnik::evm::{
+        helpers::{new_backend, new_vicinity},
+        Executor,
+    };
+    use ethers::abi::HumanReadableParser;
+    use proptest::test_runner::TestRunner;
+    use sputnik::Config;
+
+    #[test]
+    fn int_strategy_sign_extends_negative_values() {
+        // `intN`'s two's-complement range puts negative values at the top half of the `N`-bit
+        // field (high bit set); the fix requires those get sign-extended up through all 256
+        // bits, not zero-extended like a `uintN` would be.
+        let mut runner = TestRunner::default();
+        let strategy = int_strategy(8);
+
+        let mut saw_negative = false;
+        let mut saw_positive = false;
+        for _ in 0..256 {
+            let value = strategy.new_tree(&mut runner).unwrap().current();
+            if value.byte(0) & 0x80 != 0 {
+                saw_negative = true;
+                for i in 1..32 {
+                    assert_eq!(value.byte(i), 0xff, "byte {} should be sign-extended", i);
+                }
+            } else {
+                saw_positive = true;
+                for i in 1..32 {
+                    assert_eq!(value.byte(i), 0x00, "byte {} should be zero-extended for a positive value", i);
+                }
+            }
+        }
+        assert!(saw_negative, "never sampled a negative int8 value across many tries");
+        assert!(saw_positive, "never sampled a non-negative int8 value across many tries");
+    }
+
+    #[test]
+    fn fuzz_finds_and_reports_a_counterexample() {
+        let cfg = Config::istanbul();
+        let vicinity = new_vicinity();
+        let backend = new_backend(&vicinity, Default::default());
+        let mut evm = Executor::new(12_000_000, &cfg, &backend);
+
+        let target = Address::random();
+        // Reverts unconditionally, regardless of calldata -- exercises `fuzz`'s
+        // counterexample-capture path deterministically, without depending on which input the
+        // RNG happens to try first.
+        let always_reverts = vec![0x60, 0x00, 0x60, 0x00, 0xfd]; // PUSH1 0 PUSH1 0 REVERT
+        evm.initialize_contracts(vec![(target, always_reverts.into())]);
+
+        let func = HumanReadableParser::parse_function("check(uint256)").unwrap();
+
+        let counterexample = fuzz(&mut evm, &func, Address::zero(), target, 10)
+            .unwrap()
+            .expect("every call reverts, so fuzz must report a counterexample");
+
+        assert_eq!(counterexample.args.len(), 1);
+        assert!(matches!(counterexample.args[0], Token::Uint(_)));
+    }
+}
@@ -1,11 +1,11 @@
 use crate::{Evm, FAUCET_ACCOUNT};
 
-use ethers::types::{Address, Bytes, U256};
+use ethers::types::{Address, Bytes, H256, U256};
 
 use sputnik::{
     backend::{Backend, MemoryAccount},
     executor::{MemoryStackState, StackExecutor, StackState, StackSubstateMetadata},
-    Config, CreateScheme, ExitReason, ExitRevert, Transfer,
+    Config, Context, CreateScheme, ExitReason, ExitRevert, Handler, Transfer,
 };
 use std::{collections::BTreeMap, marker::PhantomData};
 
@@ -13,6 +13,11 @@ use eyre::Result;
 
 use super::SputnikExecutor;
 
+pub mod trace;
+pub mod vicinity;
+
+pub use vicinity::CheatcodeBackend;
+
 pub type MemoryState = BTreeMap<Address, MemoryAccount>;
 
 // TODO: Check if we can implement this as the base layer of an ethers-provider
@@ -47,6 +52,29 @@ impl<'a, B: Backend>
     }
 }
 
+// Concrete implementation wrapping the above in a `TracingExecutor`, for callers that want
+// `call_raw_nested_traced`'s fully nested `CallTrace`.
+impl<'a, B: Backend>
+    Executor<
+        MemoryStackState<'a, 'a, B>,
+        trace::TracingExecutor<MemoryStackState<'a, 'a, B>, StackExecutor<'a, MemoryStackState<'a, 'a, B>>>,
+    >
+{
+    /// Like [`Executor::new`], but instruments every call/create with a [`trace::Tracer`] so
+    /// that [`Executor::call_raw_nested_traced`] can return the fully nested call tree.
+    pub fn new_traced(gas_limit: u64, config: &'a Config, backend: &'a B, trace_opcodes: bool) -> Self {
+        let metadata = StackSubstateMetadata::new(gas_limit, config);
+        let state = MemoryStackState::new(metadata, backend);
+        let executor = StackExecutor::new_with_precompile(state, config, Default::default());
+
+        Self {
+            executor: trace::TracingExecutor::new(executor, trace_opcodes),
+            gas_limit,
+            marker: PhantomData,
+        }
+    }
+}
+
 // Note regarding usage of Generic vs Associated Types in traits:
 //
 // We use StackState as a trait and not as an associated type because we want to
@@ -54,7 +82,10 @@ impl<'a, B: Backend>
 // to be generic across implementations, but we don't want to make it a user-controlled generic.
 impl<'a, S, E> Evm<S> for Executor<S, E>
 where
-    E: SputnikExecutor<S>,
+    // `call_raw`'s `is_static` branch below drives `Handler::call` directly (bypassing
+    // `transact_call`'s SputnikExecutor-level wrapper), so this impl needs `E: Handler` too, not
+    // just `E: SputnikExecutor<S>` -- the latter doesn't provide `call` itself.
+    E: SputnikExecutor<S> + Handler,
     S: StackState<'a>,
 {
     type ReturnReason = ExitReason;
@@ -135,17 +166,38 @@ where
         to: Address,
         calldata: Bytes,
         value: U256,
-        _is_static: bool,
+        is_static: bool,
     ) -> Result<(Bytes, ExitReason, u64, Vec<String>)> {
         let gas_before = self.executor.gas_left();
 
-        let (status, retdata) =
-            self.executor.transact_call(from, to, value, calldata.to_vec(), self.gas_limit, vec![]);
+        // `transact_call` always enters execution with `is_static = false`, since it's meant to
+        // model a top-level transaction. To get real STATICCALL/eth_call semantics (no SSTORE,
+        // CREATE*, SELFDESTRUCT, LOG*, or value-bearing sub-calls), we go one level below and
+        // drive the `Handler::call` impl directly with the static flag set, matching EIP-214.
+        let (status, retdata) = if is_static {
+            let transfer = if value.is_zero() {
+                None
+            } else {
+                Some(Transfer { source: from, target: to, value })
+            };
+            let context = Context { address: to, caller: from, apparent_value: value };
+            self.executor.call(to, transfer, calldata.to_vec(), Some(self.gas_limit), true, context)
+        } else {
+            self.executor.transact_call(from, to, value, calldata.to_vec(), self.gas_limit, vec![])
+        };
 
         tracing::trace!(logs_before = ?self.executor.logs());
 
         let gas_after = self.executor.gas_left();
-        let gas = gas_before.saturating_sub(gas_after).saturating_sub(21000.into());
+        // `transact_call` charges the flat 21000 intrinsic transaction cost against the
+        // gasometer up front, so it has to be subtracted back out to get the cost of the call
+        // itself. `Handler::call` (the `is_static` path above) never charges it in the first
+        // place, so subtracting it again there would double-count and undercount `gas_used`.
+        let gas = if is_static {
+            gas_before.saturating_sub(gas_after)
+        } else {
+            gas_before.saturating_sub(gas_after).saturating_sub(21000.into())
+        };
 
         // get the logs
         let logs = self.executor.logs();
@@ -157,6 +209,321 @@ where
     }
 }
 
+impl<'a, S, E> Executor<S, E>
+where
+    E: SputnikExecutor<S>,
+    S: StackState<'a>,
+{
+    /// Pre-warms the executor with an EIP-2930 access list, so that reads/writes against these
+    /// addresses and storage slots are priced as "warm" (already-accessed) rather than "cold"
+    /// for the next call. Has no effect on configs that predate EIP-2929 (pre-Berlin).
+    ///
+    /// This is a plain inherent method, not an `Evm` trait method: `Evm<S>` is declared in
+    /// `lib.rs`, outside this crate's `sputnik` module, so it can't be extended from here.
+    pub fn set_access_list(&mut self, addresses: Vec<Address>, storage_keys: Vec<(Address, H256)>) {
+        let metadata = self.executor.state_mut().metadata_mut();
+        metadata.access_addresses(addresses);
+        metadata.access_storages(storage_keys);
+    }
+
+    /// Returns every address and `(address, slot)` storage key that has been accessed so far,
+    /// directly encodable into an EIP-2930 typed transaction's access list.
+    pub fn access_list(&self) -> (Vec<Address>, Vec<(Address, H256)>) {
+        match self.executor.state().metadata().accessed() {
+            Some(accessed) => (
+                accessed.accessed_addresses.iter().cloned().collect(),
+                accessed.accessed_storage.iter().cloned().collect(),
+            ),
+            None => (Vec::new(), Vec::new()),
+        }
+    }
+}
+
+impl<'a, S, E> Executor<S, E>
+where
+    E: SputnikExecutor<S> + Handler,
+    S: StackState<'a>,
+{
+    /// Like [`Evm::call_raw`], but also returns a root [`CallTrace`] for the call, so
+    /// downstream tooling can render a Foundry-style call graph and attribute gas per
+    /// sub-call instead of only getting a single aggregate number.
+    ///
+    /// This generic version can't see sub-calls made from *within* the executed bytecode: it
+    /// only knows about the single top-level call it made, since a plain `E: SputnikExecutor<S>`
+    /// (like the `StackExecutor` used by [`Executor::new`]) is its own `Handler` and recurses
+    /// into itself for nested CALL/CREATE opcodes. To get the fully nested tree, build the
+    /// `Executor` over a [`trace::TracingExecutor`] instead and call
+    /// [`Executor::call_raw_nested_traced`].
+    pub fn call_raw_traced(
+        &mut self,
+        from: Address,
+        to: Address,
+        calldata: Bytes,
+        value: U256,
+        is_static: bool,
+    ) -> Result<(Bytes, ExitReason, u64, Vec<String>, trace::CallTrace)> {
+        let call_type =
+            if is_static { trace::CallType::StaticCall } else { trace::CallType::Call };
+        let mut root = trace::CallTrace::new(call_type, from, to, value, calldata.clone(), self.gas_limit);
+
+        let (retdata, status, gas, logs) = self.call_raw(from, to, calldata, value, is_static)?;
+
+        root.output = retdata.clone();
+        root.gas_used = gas;
+        root.outcome = if Self::is_success(&status) {
+            trace::CallOutcome::Success
+        } else if matches!(status, ExitReason::Revert(_)) {
+            trace::CallOutcome::Revert
+        } else {
+            trace::CallOutcome::Error(format!("{:?}", status))
+        };
+
+        Ok((retdata, status, gas, logs, root))
+    }
+}
+
+impl<'a, S, E> Executor<S, trace::TracingExecutor<S, E>>
+where
+    // `trace::TracingExecutor<S, E>`'s own `Handler` impl requires `E: Handler`, and this
+    // block's `call_raw_nested_traced` needs `Executor<S, TracingExecutor<S, E>>: Evm<S>`,
+    // which in turn requires `TracingExecutor<S, E>: Handler` -- so `E: Handler` here too.
+    E: SputnikExecutor<S> + Handler,
+    S: StackState<'a>,
+{
+    /// Like [`Executor::call_raw_traced`], but for an `Executor` built over a
+    /// [`trace::TracingExecutor`]: since that wrapper *is* the `Handler` its own interpreter
+    /// recurses into for every CALL/CALLCODE/DELEGATECALL/STATICCALL/CREATE/CREATE2 it
+    /// encounters, the [`trace::CallTrace`] it hands back is the fully nested tree, not just a
+    /// single top-level frame. That holds for `is_static: true`, which enters through
+    /// `TracingExecutor`'s `Handler::call`; with `is_static: false` the call goes through
+    /// `TracingExecutor::transact_call`, which hands it whole to the wrapped executor, so the
+    /// returned tree holds only the root frame.
+    pub fn call_raw_nested_traced(
+        &mut self,
+        from: Address,
+        to: Address,
+        calldata: Bytes,
+        value: U256,
+        is_static: bool,
+    ) -> Result<(Bytes, ExitReason, u64, Vec<String>, trace::CallTrace)> {
+        let (retdata, status, gas, logs) = self.call_raw(from, to, calldata, value, is_static)?;
+        let root = self
+            .executor
+            .tracer
+            .root
+            .take()
+            .expect("TracingExecutor did not record a root frame for this call");
+        Ok((retdata, status, gas, logs, root))
+    }
+}
+
+/// A `Backend` that lazily pulls missing state from a JSON-RPC endpoint pinned at a specific
+/// block, caching every value it fetches so that repeated reads stay local. This is the
+/// implementation of the "replace the in-memory backend with something layered over an
+/// ethers provider" idea from the TODO above `Executor`: writes still only ever touch the
+/// local overlay, but reads of accounts/code/storage that aren't known locally transparently
+/// fall back to the remote chain.
+pub mod fork {
+    use super::*;
+    use ethers::providers::{Http, Middleware, Provider};
+    use sputnik::backend::{Basic, MemoryVicinity};
+    use std::cell::RefCell;
+    use tokio::runtime::{Handle, Runtime};
+
+    /// A `Backend` that fetches missing accounts, code and storage from a JSON-RPC provider
+    /// pinned at `vicinity.block_number`, memoizing every value it retrieves.
+    pub struct ForkBackend {
+        provider: Provider<Http>,
+        pin_block: Option<U256>,
+        vicinity: MemoryVicinity,
+        // local overlay, consulted before ever going to the network
+        state: RefCell<MemoryState>,
+        // cache of values fetched from the remote node, kept separate from `state` so we can
+        // tell which accounts/slots were actually touched for snapshotting purposes
+        basic_cache: RefCell<BTreeMap<Address, Basic>>,
+        code_cache: RefCell<BTreeMap<Address, Vec<u8>>>,
+        storage_cache: RefCell<BTreeMap<(Address, sputnik::backend::H256), sputnik::backend::H256>>,
+        block_hash_cache: RefCell<BTreeMap<U256, sputnik::backend::H256>>,
+    }
+
+    impl ForkBackend {
+        /// Creates a new fork backend pinned at `pin_block` (or the latest block, if `None`).
+        pub fn new(provider: Provider<Http>, pin_block: Option<U256>, state: MemoryState) -> Self {
+            let vicinity = Self::block_on(&provider, new_fork_vicinity(&provider, pin_block));
+            Self {
+                provider,
+                pin_block,
+                vicinity,
+                state: RefCell::new(state),
+                basic_cache: Default::default(),
+                code_cache: Default::default(),
+                storage_cache: Default::default(),
+                block_hash_cache: Default::default(),
+            }
+        }
+
+        /// Runs a future to completion, reusing the current Tokio runtime if we're already
+        /// inside one, since `Evm::call_raw` et al. are synchronous entry points.
+        fn block_on<F: std::future::Future>(_provider: &Provider<Http>, fut: F) -> F::Output {
+            match Handle::try_current() {
+                Ok(handle) => tokio::task::block_in_place(|| handle.block_on(fut)),
+                Err(_) => Runtime::new().expect("could not start tokio runtime").block_on(fut),
+            }
+        }
+
+        /// The addresses and storage slots that have been fetched from the remote node so far.
+        pub fn accessed(&self) -> (Vec<Address>, Vec<(Address, sputnik::backend::H256)>) {
+            let addresses = self.basic_cache.borrow().keys().cloned().collect();
+            let slots = self.storage_cache.borrow().keys().cloned().collect();
+            (addresses, slots)
+        }
+
+        fn basic(&self, address: Address) -> Basic {
+            if let Some(account) = self.state.borrow().get(&address) {
+                return Basic { balance: account.balance, nonce: account.nonce }
+            }
+            if let Some(basic) = self.basic_cache.borrow().get(&address) {
+                return basic.clone()
+            }
+            let (balance, nonce) = Self::block_on(&self.provider, async {
+                let balance = self.provider.get_balance(address, self.pin_block_id()).await.unwrap_or_default();
+                let nonce =
+                    self.provider.get_transaction_count(address, self.pin_block_id()).await.unwrap_or_default();
+                (balance, nonce)
+            });
+            let basic = Basic { balance, nonce };
+            self.basic_cache.borrow_mut().insert(address, basic.clone());
+            basic
+        }
+
+        fn pin_block_id(&self) -> Option<ethers::types::BlockId> {
+            let n = self.pin_block.unwrap_or(self.vicinity.block_number);
+            Some(ethers::types::BlockId::Number(n.as_u64().into()))
+        }
+    }
+
+    impl Backend for ForkBackend {
+        fn gas_price(&self) -> U256 {
+            self.vicinity.gas_price
+        }
+        fn origin(&self) -> Address {
+            self.vicinity.origin
+        }
+        fn block_hash(&self, number: U256) -> sputnik::backend::H256 {
+            // Per spec (and EIP-210), `BLOCKHASH` only resolves for the 256 most recent blocks
+            // strictly before the current one; anything else (including the current or a
+            // future block number) is the zero hash, not a lookup.
+            if number >= self.vicinity.block_number ||
+                self.vicinity.block_number - number > U256::from(256)
+            {
+                return sputnik::backend::H256::default()
+            }
+            if let Some(hash) = self.block_hash_cache.borrow().get(&number) {
+                return *hash
+            }
+            let hash = Self::block_on(
+                &self.provider,
+                self.provider.get_block(number.as_u64()),
+            )
+            .ok()
+            .flatten()
+            .and_then(|b| b.hash)
+            .unwrap_or_default();
+            self.block_hash_cache.borrow_mut().insert(number, hash);
+            hash
+        }
+        fn block_number(&self) -> U256 {
+            self.vicinity.block_number
+        }
+        fn block_coinbase(&self) -> Address {
+            self.vicinity.block_coinbase
+        }
+        fn block_timestamp(&self) -> U256 {
+            self.vicinity.block_timestamp
+        }
+        fn block_difficulty(&self) -> U256 {
+            self.vicinity.block_difficulty
+        }
+        fn block_gas_limit(&self) -> U256 {
+            self.vicinity.block_gas_limit
+        }
+        fn chain_id(&self) -> U256 {
+            self.vicinity.chain_id
+        }
+        fn exists(&self, address: Address) -> bool {
+            self.state.borrow().contains_key(&address) || self.basic(address) != Basic::default()
+        }
+        fn basic(&self, address: Address) -> Basic {
+            self.basic(address)
+        }
+        fn code(&self, address: Address) -> Vec<u8> {
+            if let Some(account) = self.state.borrow().get(&address) {
+                return account.code.clone()
+            }
+            if let Some(code) = self.code_cache.borrow().get(&address) {
+                return code.clone()
+            }
+            let code = Self::block_on(
+                &self.provider,
+                self.provider.get_code(address, self.pin_block_id()),
+            )
+            .map(|c| c.to_vec())
+            .unwrap_or_default();
+            self.code_cache.borrow_mut().insert(address, code.clone());
+            code
+        }
+        fn storage(&self, address: Address, index: sputnik::backend::H256) -> sputnik::backend::H256 {
+            if let Some(account) = self.state.borrow().get(&address) {
+                if let Some(value) = account.storage.get(&index) {
+                    return *value
+                }
+            }
+            if let Some(value) = self.storage_cache.borrow().get(&(address, index)) {
+                return *value
+            }
+            let value = Self::block_on(
+                &self.provider,
+                self.provider.get_storage_at(address, index, self.pin_block_id()),
+            )
+            .unwrap_or_default();
+            self.storage_cache.borrow_mut().insert((address, index), value);
+            value
+        }
+        fn original_storage(&self, address: Address, index: sputnik::backend::H256) -> Option<sputnik::backend::H256> {
+            Some(self.storage(address, index))
+        }
+    }
+
+    /// Like [`super::helpers::new_vicinity`], but populates the block environment fields
+    /// (number, timestamp, coinbase, chain id) from the remote node at `pin_block` instead of
+    /// using defaults. Block hashes are deliberately *not* fetched here: `ForkBackend` stays
+    /// lazy and resolves (and caches) each one on demand in `block_hash()` instead of paying
+    /// for up to 256 RPC round-trips up front.
+    async fn new_fork_vicinity(provider: &Provider<Http>, pin_block: Option<U256>) -> MemoryVicinity {
+        let block_id = pin_block.map(|n| ethers::types::BlockId::Number(n.as_u64().into()));
+        let block =
+            provider.get_block(block_id.unwrap_or(ethers::types::BlockId::Number(Default::default()))).await.expect(
+                "could not fetch pinned block",
+            ).expect("pinned block does not exist");
+
+        let chain_id = provider.get_chainid().await.unwrap_or_default();
+
+        let number = block.number.unwrap_or_default();
+
+        MemoryVicinity {
+            gas_price: U256::zero(),
+            origin: Address::zero(),
+            block_hashes: Vec::new(),
+            block_number: number,
+            block_coinbase: block.author.unwrap_or_default(),
+            block_timestamp: block.timestamp,
+            block_difficulty: block.difficulty,
+            block_gas_limit: block.gas_limit,
+            chain_id,
+        }
+    }
+}
+
 #[cfg(any(test, feature = "sputnik-helpers"))]
 pub mod helpers {
     use super::*;
@@ -296,4 +663,127 @@ mod tests {
         // the call must be successful
         assert!(matches!(res.1, ExitReason::Succeed(_)));
     }
+
+    #[test]
+    fn static_call_reverts_on_sstore() {
+        let cfg = Config::istanbul();
+
+        let vicinity = new_vicinity();
+        let backend = new_backend(&vicinity, Default::default());
+        let mut evm = Executor::new(12_000_000, &cfg, &backend);
+
+        // PUSH1 0x01 PUSH1 0x00 SSTORE
+        let code = vec![0x60, 0x01, 0x60, 0x00, 0x55];
+        let addr = Address::random();
+        evm.initialize_contracts(vec![(addr, code.into())]);
+
+        let (_, status, _, _) =
+            evm.call_raw(Address::zero(), addr, Bytes::default(), 0.into(), true).unwrap();
+        assert!(!matches!(status, ExitReason::Succeed(_)));
+    }
+
+    #[test]
+    fn static_call_reports_nonzero_gas_used() {
+        let cfg = Config::istanbul();
+
+        let vicinity = new_vicinity();
+        let backend = new_backend(&vicinity, Default::default());
+        let mut evm = Executor::new(12_000_000, &cfg, &backend);
+
+        // PUSH1 0x2a PUSH1 0x00 MSTORE PUSH1 0x20 PUSH1 0x00 RETURN, a cheap view-style getter
+        // that costs well under 21000 gas, so a double-subtracted intrinsic cost would saturate
+        // the reported `gas_used` to zero.
+        let code = vec![
+            0x60, 0x2a, 0x60, 0x00, 0x52, 0x60, 0x20, 0x60, 0x00, 0xf3,
+        ];
+        let addr = Address::random();
+        evm.initialize_contracts(vec![(addr, code.into())]);
+
+        let (retdata, status, gas_used, _) =
+            evm.call_raw(Address::zero(), addr, Bytes::default(), 0.into(), true).unwrap();
+        assert!(matches!(status, ExitReason::Succeed(_)));
+        assert!(!retdata.as_ref().is_empty());
+        assert!(gas_used > 0);
+        assert!(gas_used < 21000);
+    }
+
+    #[test]
+    fn warm_sload_is_cheaper_than_cold() {
+        let cfg = Config::london();
+
+        // PUSH1 0x00 SLOAD POP, run twice so the second read hits an already-accessed slot
+        let code = vec![0x60, 0x00, 0x54, 0x50, 0x60, 0x00, 0x54, 0x50];
+        let addr = Address::random();
+
+        let vicinity = new_vicinity();
+        let backend = new_backend(&vicinity, Default::default());
+        let mut evm = Executor::new(12_000_000, &cfg, &backend);
+        evm.initialize_contracts(vec![(addr, code.clone().into())]);
+        let (_, _, cold_gas, _) =
+            evm.call_raw(Address::zero(), addr, Bytes::default(), 0.into(), false).unwrap();
+
+        let vicinity = new_vicinity();
+        let backend = new_backend(&vicinity, Default::default());
+        let mut evm = Executor::new(12_000_000, &cfg, &backend);
+        evm.initialize_contracts(vec![(addr, code.into())]);
+        evm.set_access_list(vec![], vec![(addr, H256::zero())]);
+        let (_, _, warm_gas, _) =
+            evm.call_raw(Address::zero(), addr, Bytes::default(), 0.into(), false).unwrap();
+
+        assert!(warm_gas < cold_gas);
+    }
+
+    #[test]
+    fn can_warp_block_number_and_timestamp() {
+        let cfg = Config::istanbul();
+
+        let vicinity = new_vicinity();
+        let inner = new_backend(&vicinity, Default::default());
+        let backend = CheatcodeBackend::new(inner, vicinity.clone());
+        let mut evm = Executor::new(12_000_000, &cfg, &backend);
+
+        evm.set_block_number(1234.into());
+        evm.set_block_timestamp(5678.into());
+        evm.set_coinbase(Address::repeat_byte(0xAA));
+
+        let vicinity = evm.executor.state().backend().vicinity.borrow();
+        assert_eq!(vicinity.block_number, U256::from(1234));
+        assert_eq!(vicinity.block_timestamp, U256::from(5678));
+        assert_eq!(vicinity.block_coinbase, Address::repeat_byte(0xAA));
+    }
+
+    #[test]
+    fn nested_call_trace_has_one_child() {
+        let cfg = Config::istanbul();
+
+        let vicinity = new_vicinity();
+        let backend = new_backend(&vicinity, Default::default());
+        let mut evm = Executor::new_traced(12_000_000, &cfg, &backend, false);
+
+        let callee = Address::random();
+        let caller = Address::random();
+
+        // callee: STOP
+        let callee_code = vec![0x00];
+
+        // caller: CALL(gas(), callee, 0, 0, 0, 0, 0); STOP
+        let mut caller_code = vec![0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x60, 0x00, 0x73];
+        caller_code.extend_from_slice(callee.as_bytes());
+        caller_code.push(0x5a); // GAS
+        caller_code.push(0xf1); // CALL
+        caller_code.push(0x00); // STOP
+
+        evm.initialize_contracts(vec![(callee, callee_code.into()), (caller, caller_code.into())]);
+
+        let (_, status, _, _, root) = evm
+            .call_raw_nested_traced(Address::zero(), caller, Bytes::default(), 0.into(), false)
+            .unwrap();
+        assert!(matches!(status, ExitReason::Succeed(_)));
+        assert_eq!(root.children.len(), 1);
+        assert_eq!(root.children[0].to, callee);
+        assert_eq!(root.children[0].call_type, trace::CallType::Call);
+        assert!(root.children[0].gas_used > 0, "child frame should report real gas usage, not 0");
+        assert!(root.gas_used >= root.children[0].gas_used);
+        assert!(root.total_gas_used() >= root.gas_used);
+    }
 }
@@ -0,0 +1,484 @@
+//! Structured call/opcode tracing for the Sputnik `Executor`, modeled on the
+//! `Tracer`/`VMTracer` split from OpenEthereum: a `CallTrace` records the nested tree of
+//! sub-calls a transaction makes, while `VmStep`s record the opcode-level steps of the whole
+//! execution for deep debugging.
+
+use ethers::types::{Address, Bytes, U256};
+
+use sputnik::{
+    executor::StackState, Capture, Context, CreateScheme, ExitReason, Handler, Runtime, Transfer,
+};
+use std::{marker::PhantomData, rc::Rc};
+
+use super::SputnikExecutor;
+
+/// The EVM instruction that opened a given call frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CallType {
+    Call,
+    CallCode,
+    DelegateCall,
+    StaticCall,
+    Create,
+    Create2,
+}
+
+/// How a call frame finished.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CallOutcome {
+    Success,
+    Revert,
+    /// An EVM error other than a revert (out of gas, invalid opcode, static-mode violation, ...).
+    Error(String),
+}
+
+/// A single frame in the call tree: either the top-level transaction or a CALL/CALLCODE/
+/// DELEGATECALL/STATICCALL/CREATE/CREATE2 made from within it.
+#[derive(Debug, Clone)]
+pub struct CallTrace {
+    pub call_type: CallType,
+    pub from: Address,
+    pub to: Address,
+    pub value: U256,
+    pub input: Bytes,
+    pub output: Bytes,
+    pub gas: u64,
+    pub gas_used: u64,
+    pub outcome: CallOutcome,
+    pub children: Vec<CallTrace>,
+}
+
+impl CallTrace {
+    pub fn new(call_type: CallType, from: Address, to: Address, value: U256, input: Bytes, gas: u64) -> Self {
+        Self {
+            call_type,
+            from,
+            to,
+            value,
+            input,
+            output: Bytes::default(),
+            gas,
+            gas_used: 0,
+            outcome: CallOutcome::Success,
+            children: Vec::new(),
+        }
+    }
+
+    /// Total gas spent by this frame plus every descendant, for attributing gas per sub-call.
+    pub fn total_gas_used(&self) -> u64 {
+        self.gas_used + self.children.iter().map(|c| c.total_gas_used()).sum::<u64>()
+    }
+}
+
+/// A single opcode-level step, recorded when opcode tracing is enabled.
+#[derive(Debug, Clone)]
+pub struct VmStep {
+    pub pc: usize,
+    pub opcode: String,
+    pub gas_remaining: u64,
+    pub stack_depth: usize,
+}
+
+/// Accumulates `CallTrace` frames as execution descends into sub-calls and `VmStep`s as
+/// instructions are stepped through, if opcode tracing is enabled.
+#[derive(Debug, Default)]
+pub struct Tracer {
+    /// Stack of in-progress call frames; the last entry is the currently executing one.
+    stack: Vec<CallTrace>,
+    /// The finished root frame, set once the top-level call/create returns.
+    pub root: Option<CallTrace>,
+    /// Opcode-level steps, flat across the whole execution; correlate with a frame via
+    /// `stack_depth`.
+    pub steps: Vec<VmStep>,
+    pub trace_opcodes: bool,
+}
+
+impl Tracer {
+    pub fn new(trace_opcodes: bool) -> Self {
+        Self { trace_opcodes, ..Default::default() }
+    }
+
+    /// Call when execution enters a new frame (CALL/CALLCODE/DELEGATECALL/STATICCALL/CREATE*).
+    pub fn start_call(
+        &mut self,
+        call_type: CallType,
+        from: Address,
+        to: Address,
+        value: U256,
+        input: Bytes,
+        gas: u64,
+    ) {
+        self.stack.push(CallTrace::new(call_type, from, to, value, input, gas));
+    }
+
+    /// Fills in the `to` address of the currently open frame, for call types (namely CREATE/
+    /// CREATE2) where it isn't known until after the callee has actually run.
+    pub fn set_current_to(&mut self, to: Address) {
+        if let Some(frame) = self.stack.last_mut() {
+            frame.to = to;
+        }
+    }
+
+    /// Call when the current frame returns, nesting it under its parent (if any).
+    pub fn end_call(&mut self, output: Bytes, gas_used: u64, outcome: CallOutcome) {
+        let mut frame = self.stack.pop().expect("end_call without a matching start_call");
+        frame.output = output;
+        frame.gas_used = gas_used;
+        frame.outcome = outcome;
+
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(frame),
+            None => self.root = Some(frame),
+        }
+    }
+
+    /// Records a single opcode step, if opcode-level tracing is enabled.
+    pub fn step(&mut self, pc: usize, opcode: String, gas_remaining: u64) {
+        if self.trace_opcodes {
+            self.steps.push(VmStep { pc, opcode, gas_remaining, stack_depth: self.stack.len() });
+        }
+    }
+}
+
+/// A [`SputnikExecutor`] that instruments every CALL/CALLCODE/DELEGATECALL/STATICCALL/
+/// CREATE/CREATE2 with a [`Tracer`] frame.
+///
+/// Wrapping `inner`'s `call`/`create` and simply forwarding to `inner.call(...)`/
+/// `inner.create(...)` is not enough to see *nested* sub-calls: `inner` is itself the
+/// `Handler` its own interpreter runs against, so any CALL opcode `inner` encounters while
+/// executing recurses back into `inner`'s `Handler` impl, never into ours. To actually see
+/// every frame, `TracingExecutor` has to be the `Handler` the interpreter runs against for
+/// the *entire* execution, not just its entry point. So instead of delegating the whole call,
+/// `call`/`create` here do only the one thing `inner` can't delegate (fetching the callee's
+/// code and building a fresh `Runtime` for it) and then run that `Runtime` against `self` --
+/// which means any CALL-family opcode it hits comes back through `TracingExecutor::call`/
+/// `create` again, nesting correctly under the frame that triggered it.
+///
+/// Since that means we're no longer going through `inner`'s own `call`/`create`, we have to
+/// reproduce the substate bookkeeping those would otherwise have done for us: `enter` a fresh
+/// checkpoint before running the sub-call's code, and `exit_commit`/`exit_revert`/`exit_discard`
+/// it afterwards depending on the outcome, so a reverted sub-call's writes don't leak into the
+/// parent frame. `create` additionally computes the deployment address from the caller's
+/// current nonce and then bumps that nonce itself, so two sequential CREATEs from the same
+/// caller don't collide.
+///
+/// The `SputnikExecutor` entry points `transact_call`/`transact_create` hand the whole
+/// transaction to `inner` and record only the root frame: nested frames are recorded only for
+/// calls and creates that enter through this type's `Handler::call`/`Handler::create`.
+pub struct TracingExecutor<S, E> {
+    pub inner: E,
+    pub tracer: Tracer,
+    marker: PhantomData<S>,
+}
+
+impl<S, E> TracingExecutor<S, E> {
+    pub fn new(inner: E, trace_opcodes: bool) -> Self {
+        Self { inner, tracer: Tracer::new(trace_opcodes), marker: PhantomData }
+    }
+}
+
+impl<'a, S: StackState<'a>, E: SputnikExecutor<S>> SputnikExecutor<S> for TracingExecutor<S, E> {
+    fn config(&self) -> &sputnik::Config {
+        self.inner.config()
+    }
+    fn state(&self) -> &S {
+        self.inner.state()
+    }
+    fn state_mut(&mut self) -> &mut S {
+        self.inner.state_mut()
+    }
+    fn gas_left(&self) -> ethers::types::U256 {
+        self.inner.gas_left()
+    }
+    fn logs(&self) -> Vec<String> {
+        self.inner.logs()
+    }
+    fn clear_logs(&mut self) {
+        self.inner.clear_logs()
+    }
+    fn create_address(&self, scheme: CreateScheme) -> Address {
+        self.inner.create_address(scheme)
+    }
+    fn transact_create(
+        &mut self,
+        caller: Address,
+        value: U256,
+        init_code: Vec<u8>,
+        gas_limit: u64,
+        access_list: Vec<(Address, Vec<sputnik::backend::H256>)>,
+    ) -> ExitReason {
+        let to = self.inner.create_address(CreateScheme::Legacy { caller });
+        self.tracer.start_call(
+            CallType::Create,
+            caller,
+            to,
+            value,
+            init_code.clone().into(),
+            gas_limit,
+        );
+        let gas_before = self.inner.gas_left();
+        let status = self.inner.transact_create(caller, value, init_code, gas_limit, access_list);
+        let gas_used = gas_before.saturating_sub(self.inner.gas_left()).as_u64();
+        self.tracer.end_call(Bytes::default(), gas_used, outcome_of(&status));
+        status
+    }
+    fn transact_call(
+        &mut self,
+        caller: Address,
+        address: Address,
+        value: U256,
+        data: Vec<u8>,
+        gas_limit: u64,
+        access_list: Vec<(Address, Vec<sputnik::backend::H256>)>,
+    ) -> (ExitReason, Vec<u8>) {
+        self.tracer.start_call(CallType::Call, caller, address, value, data.clone().into(), gas_limit);
+        let gas_before = self.inner.gas_left();
+        let (status, retdata) =
+            self.inner.transact_call(caller, address, value, data, gas_limit, access_list);
+        let gas_used = gas_before.saturating_sub(self.inner.gas_left()).as_u64();
+        self.tracer.end_call(retdata.clone().into(), gas_used, outcome_of(&status));
+        (status, retdata)
+    }
+}
+
+impl<'a, S: StackState<'a>, E: SputnikExecutor<S> + Handler> Handler for TracingExecutor<S, E> {
+    type CreateInterrupt = E::CreateInterrupt;
+    type CreateFeedback = E::CreateFeedback;
+    type CallInterrupt = E::CallInterrupt;
+    type CallFeedback = E::CallFeedback;
+
+    fn balance(&self, address: Address) -> U256 {
+        self.inner.balance(address)
+    }
+    fn code_size(&self, address: Address) -> U256 {
+        self.inner.code_size(address)
+    }
+    fn code_hash(&self, address: Address) -> sputnik::backend::H256 {
+        self.inner.code_hash(address)
+    }
+    fn code(&self, address: Address) -> Vec<u8> {
+        self.inner.code(address)
+    }
+    fn storage(&self, address: Address, index: sputnik::backend::H256) -> sputnik::backend::H256 {
+        self.inner.storage(address, index)
+    }
+    fn original_storage(
+        &self,
+        address: Address,
+        index: sputnik::backend::H256,
+    ) -> sputnik::backend::H256 {
+        self.inner.original_storage(address, index)
+    }
+    fn gas_left(&self) -> U256 {
+        Handler::gas_left(&self.inner)
+    }
+    fn gas_price(&self) -> U256 {
+        self.inner.gas_price()
+    }
+    fn origin(&self) -> Address {
+        self.inner.origin()
+    }
+    fn block_hash(&self, number: U256) -> sputnik::backend::H256 {
+        self.inner.block_hash(number)
+    }
+    fn block_number(&self) -> U256 {
+        self.inner.block_number()
+    }
+    fn block_coinbase(&self) -> Address {
+        self.inner.block_coinbase()
+    }
+    fn block_timestamp(&self) -> U256 {
+        self.inner.block_timestamp()
+    }
+    fn block_difficulty(&self) -> U256 {
+        self.inner.block_difficulty()
+    }
+    fn block_gas_limit(&self) -> U256 {
+        self.inner.block_gas_limit()
+    }
+    fn chain_id(&self) -> U256 {
+        self.inner.chain_id()
+    }
+    fn exists(&self, address: Address) -> bool {
+        self.inner.exists(address)
+    }
+    fn deleted(&self, address: Address) -> bool {
+        self.inner.deleted(address)
+    }
+    fn is_cold(&self, address: Address, index: Option<sputnik::backend::H256>) -> bool {
+        self.inner.is_cold(address, index)
+    }
+    fn set_storage(
+        &mut self,
+        address: Address,
+        index: sputnik::backend::H256,
+        value: sputnik::backend::H256,
+    ) -> Result<(), sputnik::ExitError> {
+        self.inner.set_storage(address, index, value)
+    }
+    fn log(
+        &mut self,
+        address: Address,
+        topics: Vec<sputnik::backend::H256>,
+        data: Vec<u8>,
+    ) -> Result<(), sputnik::ExitError> {
+        self.inner.log(address, topics, data)
+    }
+    fn mark_delete(&mut self, address: Address, target: Address) -> Result<(), sputnik::ExitError> {
+        self.inner.mark_delete(address, target)
+    }
+    fn pre_validate(
+        &mut self,
+        context: &Context,
+        opcode: sputnik::Opcode,
+        stack: &sputnik::Stack,
+    ) -> Result<(), sputnik::ExitError> {
+        self.inner.pre_validate(context, opcode, stack)
+    }
+
+    fn create(
+        &mut self,
+        caller: Address,
+        scheme: CreateScheme,
+        value: U256,
+        init_code: Vec<u8>,
+        target_gas: Option<u64>,
+    ) -> (ExitReason, Option<Address>, Vec<u8>) {
+        // The frame starts with a placeholder `to`, patched below once the create address has
+        // been computed.
+        let call_type =
+            if matches!(scheme, CreateScheme::Create2 { .. }) { CallType::Create2 } else { CallType::Create };
+        let gas_limit = target_gas.unwrap_or_default();
+        self.tracer.start_call(
+            call_type,
+            caller,
+            Address::zero(),
+            value,
+            init_code.clone().into(),
+            gas_limit,
+        );
+
+        // Mirrors `StackExecutor::create`'s own bookkeeping: compute the create address from
+        // the caller's current nonce, then bump that nonce so two sequential CREATEs from the
+        // same caller don't collide, then enter a new checkpoint so a reverted/errored
+        // sub-create rolls its writes back instead of leaking into the parent frame.
+        let to = self.inner.create_address(scheme);
+        self.tracer.set_current_to(to);
+        self.inner.state_mut().inc_nonce(caller);
+        self.inner.state_mut().enter(gas_limit, false);
+
+        if !value.is_zero() {
+            if let Err(e) =
+                self.inner.state_mut().transfer(Transfer { source: caller, target: to, value })
+            {
+                let _ = self.inner.state_mut().exit_discard();
+                self.tracer.end_call(Bytes::default(), 0, CallOutcome::Error(format!("{:?}", e)));
+                return (ExitReason::Error(e), None, Vec::new())
+            }
+        }
+
+        let context = Context { address: to, caller, apparent_value: value };
+
+        let gas_before = Handler::gas_left(&self.inner);
+        let code = Rc::new(init_code);
+        let mut runtime = Runtime::new(code, Rc::new(Vec::new()), context, self.inner.config());
+        let reason = match runtime.run(self) {
+            Capture::Exit(reason) => reason,
+            Capture::Trap(_) => unreachable!("CREATE init code does not trap"),
+        };
+        let gas_used = gas_before.saturating_sub(Handler::gas_left(&self.inner)).as_u64();
+        let retdata = runtime.machine().return_value();
+
+        let exit_result = match reason {
+            ExitReason::Succeed(_) => {
+                self.inner.state_mut().set_code(to, retdata.clone());
+                self.inner.state_mut().exit_commit()
+            }
+            ExitReason::Revert(_) => self.inner.state_mut().exit_revert(),
+            _ => self.inner.state_mut().exit_discard(),
+        };
+        if let Err(e) = exit_result {
+            self.tracer.end_call(retdata.clone().into(), gas_used, CallOutcome::Error(format!("{:?}", e)));
+            return (ExitReason::Error(e), None, retdata)
+        }
+
+        self.tracer.end_call(retdata.clone().into(), gas_used, outcome_of(&reason));
+
+        let address = if matches!(reason, ExitReason::Succeed(_)) { Some(to) } else { None };
+        (reason, address, retdata)
+    }
+
+    fn call(
+        &mut self,
+        code_address: Address,
+        transfer: Option<Transfer>,
+        input: Vec<u8>,
+        target_gas: Option<u64>,
+        is_static: bool,
+        context: Context,
+    ) -> (ExitReason, Vec<u8>) {
+        // CALLCODE and DELEGATECALL run `code_address`'s code in the calling contract's own
+        // context: CALLCODE with that contract as the caller, DELEGATECALL keeping its caller.
+        let call_type = if is_static {
+            CallType::StaticCall
+        } else if context.address == code_address {
+            CallType::Call
+        } else if context.caller == context.address {
+            CallType::CallCode
+        } else {
+            CallType::DelegateCall
+        };
+        let gas_limit = target_gas.unwrap_or_default();
+        self.tracer.start_call(
+            call_type,
+            context.caller,
+            code_address,
+            context.apparent_value,
+            input.clone().into(),
+            gas_limit,
+        );
+
+        // See the matching comment in `create` above: checkpoint so a reverted/errored
+        // sub-call's writes don't leak into the parent frame.
+        self.inner.state_mut().enter(gas_limit, is_static);
+
+        if let Some(transfer) = transfer {
+            if let Err(e) = self.inner.state_mut().transfer(transfer) {
+                let _ = self.inner.state_mut().exit_discard();
+                self.tracer.end_call(Bytes::default(), 0, CallOutcome::Error(format!("{:?}", e)));
+                return (ExitReason::Error(e), Vec::new())
+            }
+        }
+
+        let gas_before = Handler::gas_left(&self.inner);
+        let code = Rc::new(Handler::code(&self.inner, code_address));
+        let mut runtime = Runtime::new(code, Rc::new(input), context, self.inner.config());
+        let reason = match runtime.run(self) {
+            Capture::Exit(reason) => reason,
+            Capture::Trap(_) => unreachable!("CALL code does not trap"),
+        };
+        let gas_used = gas_before.saturating_sub(Handler::gas_left(&self.inner)).as_u64();
+        let retdata = runtime.machine().return_value();
+
+        let exit_result = match reason {
+            ExitReason::Succeed(_) => self.inner.state_mut().exit_commit(),
+            ExitReason::Revert(_) => self.inner.state_mut().exit_revert(),
+            _ => self.inner.state_mut().exit_discard(),
+        };
+        if let Err(e) = exit_result {
+            self.tracer.end_call(retdata.clone().into(), gas_used, CallOutcome::Error(format!("{:?}", e)));
+            return (ExitReason::Error(e), retdata)
+        }
+
+        self.tracer.end_call(retdata.clone().into(), gas_used, outcome_of(&reason));
+        (reason, retdata)
+    }
+}
+
+fn outcome_of(status: &ExitReason) -> CallOutcome {
+    match status {
+        ExitReason::Succeed(_) => CallOutcome::Success,
+        ExitReason::Revert(_) => CallOutcome::Revert,
+        other => CallOutcome::Error(format!("{:?}", other)),
+    }
+}
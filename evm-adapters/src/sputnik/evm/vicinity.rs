@@ -0,0 +1,108 @@
+//! A `Backend` wrapper whose block environment can be mutated after construction, so cheatcodes
+//! can warp `block.number`/`block.timestamp`/`block.coinbase`/`chain_id` between calls without
+//! rebuilding the backend, state and executor from scratch.
+
+use super::*;
+use sputnik::backend::{Basic, MemoryVicinity};
+use std::cell::RefCell;
+
+/// Wraps a `Backend` together with an owned, interior-mutable [`MemoryVicinity`]. Account state
+/// (balance, nonce, code, storage) is delegated straight through to the wrapped backend; only
+/// the block-environment fields are served from (and can be rewritten via) `vicinity`.
+pub struct CheatcodeBackend<B> {
+    pub backend: B,
+    pub vicinity: RefCell<MemoryVicinity>,
+}
+
+impl<B> CheatcodeBackend<B> {
+    pub fn new(backend: B, vicinity: MemoryVicinity) -> Self {
+        Self { backend, vicinity: RefCell::new(vicinity) }
+    }
+}
+
+impl<B: Backend> Backend for CheatcodeBackend<B> {
+    fn gas_price(&self) -> U256 {
+        self.vicinity.borrow().gas_price
+    }
+    fn origin(&self) -> Address {
+        self.vicinity.borrow().origin
+    }
+    fn block_hash(&self, number: U256) -> H256 {
+        let vicinity = self.vicinity.borrow();
+        let current = vicinity.block_number;
+        if number >= current || current - number > U256::from(vicinity.block_hashes.len()) {
+            H256::default()
+        } else {
+            let index = (current - number - 1).as_usize();
+            vicinity.block_hashes[vicinity.block_hashes.len() - 1 - index]
+        }
+    }
+    fn block_number(&self) -> U256 {
+        self.vicinity.borrow().block_number
+    }
+    fn block_coinbase(&self) -> Address {
+        self.vicinity.borrow().block_coinbase
+    }
+    fn block_timestamp(&self) -> U256 {
+        self.vicinity.borrow().block_timestamp
+    }
+    fn block_difficulty(&self) -> U256 {
+        self.vicinity.borrow().block_difficulty
+    }
+    fn block_gas_limit(&self) -> U256 {
+        self.vicinity.borrow().block_gas_limit
+    }
+    fn chain_id(&self) -> U256 {
+        self.vicinity.borrow().chain_id
+    }
+    fn exists(&self, address: Address) -> bool {
+        self.backend.exists(address)
+    }
+    fn basic(&self, address: Address) -> Basic {
+        self.backend.basic(address)
+    }
+    fn code(&self, address: Address) -> Vec<u8> {
+        self.backend.code(address)
+    }
+    fn storage(&self, address: Address, index: H256) -> H256 {
+        self.backend.storage(address, index)
+    }
+    fn original_storage(&self, address: Address, index: H256) -> Option<H256> {
+        self.backend.original_storage(address, index)
+    }
+}
+
+// Cheatcode methods for warping the block environment of an executor backed by a
+// `CheatcodeBackend`, between `call_raw`/`deploy` invocations.
+//
+// These intentionally stay inherent methods on this one concrete instantiation rather than
+// becoming `Evm` trait methods (even with a default no-op body): `Evm<S>` is implemented by a
+// *single* blanket `impl<'a, S, E> Evm<S> for Executor<S, E>` covering every `S: StackState<'a>`,
+// so one method body has to serve every backend. There is no sound way in stable Rust for that
+// one body to both no-op for an arbitrary backend and mutate `CheatcodeBackend`'s vicinity for
+// this one — that's specialization, which isn't stable, and the backends here aren't `'static`
+// (they borrow `'a` state), so an `Any`-downcast escape hatch isn't available either. Keeping
+// these as inherent methods on the concrete `CheatcodeBackend`-backed `Executor` is the accurate
+// representation of "only available when you're holding a cheatcode-enabled executor".
+impl<'a, B: Backend>
+    Executor<
+        MemoryStackState<'a, 'a, CheatcodeBackend<B>>,
+        StackExecutor<'a, MemoryStackState<'a, 'a, CheatcodeBackend<B>>>,
+    >
+{
+    pub fn set_block_number(&mut self, number: U256) {
+        self.executor.state().backend().vicinity.borrow_mut().block_number = number;
+    }
+
+    pub fn set_block_timestamp(&mut self, timestamp: U256) {
+        self.executor.state().backend().vicinity.borrow_mut().block_timestamp = timestamp;
+    }
+
+    pub fn set_coinbase(&mut self, coinbase: Address) {
+        self.executor.state().backend().vicinity.borrow_mut().block_coinbase = coinbase;
+    }
+
+    pub fn set_chain_id(&mut self, chain_id: U256) {
+        self.executor.state().backend().vicinity.borrow_mut().chain_id = chain_id;
+    }
+}